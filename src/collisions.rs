@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use crate::{Node, Point};
+
+/// A uniform spatial hash with one cell per `2 * radius`, so two nodes can
+/// only overlap if they land in the same or an adjacent cell.
+fn cell_of(position: Point, cell_size: f32) -> (i64, i64) {
+    (
+        (position.0 / cell_size).floor() as i64,
+        (position.1 / cell_size).floor() as i64,
+    )
+}
+
+/// Resolves overlapping node pairs with 2D elastic collisions: the
+/// velocity components along the line connecting the two centers are
+/// exchanged, the tangential components are left untouched, and the pair
+/// is pushed apart to remove any penetration. Candidate pairs are found
+/// via a uniform spatial hash keyed by cell, so the cost stays near-linear
+/// as the node count grows past what an O(n^2) scan could handle.
+pub fn resolve_collisions(nodes: &mut [Node], radius: f32) {
+    let cell_size = radius * 2.0;
+    let mut buckets: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (index, node) in nodes.iter().enumerate() {
+        buckets
+            .entry(cell_of(node.position, cell_size))
+            .or_default()
+            .push(index);
+    }
+
+    // Only look at each "forward" half of a cell's neighborhood, plus the
+    // cell itself, so every pair of cells is considered exactly once.
+    const NEIGHBOR_OFFSETS: [(i64, i64); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    for (&cell, indices) in &buckets {
+        for a in 0..indices.len() {
+            for b in (a + 1)..indices.len() {
+                pairs.push((indices[a], indices[b]));
+            }
+        }
+        for (dx, dy) in NEIGHBOR_OFFSETS {
+            let Some(neighbor_indices) = buckets.get(&(cell.0 + dx, cell.1 + dy)) else {
+                continue;
+            };
+            for &a in indices {
+                for &b in neighbor_indices {
+                    pairs.push((a, b));
+                }
+            }
+        }
+    }
+
+    for (a, b) in pairs {
+        let (i, j) = if a < b { (a, b) } else { (b, a) };
+        let (left, right) = nodes.split_at_mut(j);
+        resolve_pair(&mut left[i], &mut right[0], radius);
+    }
+}
+
+fn resolve_pair(a: &mut Node, b: &mut Node, radius: f32) {
+    let delta = (b.position.0 - a.position.0, b.position.1 - a.position.1);
+    let distance = (delta.0.powi(2) + delta.1.powi(2)).sqrt();
+    let min_distance = radius * 2.0;
+    if distance == 0.0 || distance >= min_distance {
+        return;
+    }
+    let normal = (delta.0 / distance, delta.1 / distance);
+
+    let overlap = (min_distance - distance) / 2.0;
+    a.position.0 -= normal.0 * overlap;
+    a.position.1 -= normal.1 * overlap;
+    b.position.0 += normal.0 * overlap;
+    b.position.1 += normal.1 * overlap;
+
+    let a_along = a.velocity.vx * normal.0 + a.velocity.vy * normal.1;
+    let b_along = b.velocity.vx * normal.0 + b.velocity.vy * normal.1;
+    let a_tangent = (
+        a.velocity.vx - a_along * normal.0,
+        a.velocity.vy - a_along * normal.1,
+    );
+    let b_tangent = (
+        b.velocity.vx - b_along * normal.0,
+        b.velocity.vy - b_along * normal.1,
+    );
+
+    a.velocity.vx = a_tangent.0 + b_along * normal.0;
+    a.velocity.vy = a_tangent.1 + b_along * normal.1;
+    b.velocity.vx = b_tangent.0 + a_along * normal.0;
+    b.velocity.vy = b_tangent.1 + a_along * normal.1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Velocity;
+
+    fn node_at(x: f32, y: f32, vx: f32, vy: f32) -> Node {
+        Node {
+            position: (x, y),
+            velocity: Velocity { vx, vy },
+            previous_coord: (x as usize, y as usize),
+        }
+    }
+
+    #[test]
+    fn test_overlapping_head_on_nodes_exchange_velocity_and_separate() {
+        let mut nodes = vec![node_at(5.0, 5.0, 1.0, 0.0), node_at(5.4, 5.0, -1.0, 0.0)];
+        resolve_collisions(&mut nodes, 0.3);
+
+        assert!((nodes[0].velocity.vx - -1.0).abs() < 1e-5);
+        assert!((nodes[1].velocity.vx - 1.0).abs() < 1e-5);
+        assert!(nodes[1].position.0 - nodes[0].position.0 >= 0.6 - 1e-5);
+    }
+
+    #[test]
+    fn test_distant_nodes_are_left_alone() {
+        let mut nodes = vec![node_at(1.0, 1.0, 1.0, 0.0), node_at(10.0, 10.0, -1.0, 0.0)];
+        resolve_collisions(&mut nodes, 0.3);
+
+        assert_eq!(nodes[0].velocity.vx, 1.0);
+        assert_eq!(nodes[1].velocity.vx, -1.0);
+    }
+}
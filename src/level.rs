@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use crate::raster::supercover;
+use crate::{Coordinate, Line, Point, Size};
+
+/// A wall segment paired with the outward unit normal nodes should bounce
+/// off of.
+#[derive(Copy, Clone, Debug)]
+pub struct Wall {
+    pub line: Line,
+    pub normal: Point,
+}
+
+/// Every wall in the play area, indexed by a uniform grid so a moving node
+/// only has to test walls near its travel path instead of the whole list.
+pub struct Level {
+    walls: Vec<Wall>,
+    grid: HashMap<Coordinate, Vec<usize>>,
+    bounds: Size,
+}
+
+impl Level {
+    /// Builds a level from an arbitrary set of walls, bucketing each one
+    /// into every grid cell its segment passes through (see `cells_touched`).
+    pub fn new(walls: Vec<Wall>, bounds: Size) -> Self {
+        let mut grid: HashMap<Coordinate, Vec<usize>> = HashMap::new();
+        for (index, wall) in walls.iter().enumerate() {
+            for cell in cells_touched(wall.line, bounds) {
+                grid.entry(cell).or_default().push(index);
+            }
+        }
+        Level {
+            walls,
+            grid,
+            bounds,
+        }
+    }
+
+    /// A level with just the four walls bounding `bounds` and no interior
+    /// obstacles. `main()` builds its level via `with_layout` so it gets
+    /// the interior obstacles too; this is test-only, for cases that want
+    /// a bare boundary without pulling in `DEFAULT_LAYOUT`.
+    #[cfg(test)]
+    pub fn boundary(bounds: Size) -> Self {
+        Level::new(boundary_walls(bounds), bounds)
+    }
+
+    /// A level with the four boundary walls plus interior obstacle walls
+    /// parsed from `layout` (see [`obstacles_from_layout`]).
+    pub fn with_layout(layout: &str, bounds: Size) -> Self {
+        let mut walls = boundary_walls(bounds);
+        walls.extend(obstacles_from_layout(layout, bounds));
+        Level::new(walls, bounds)
+    }
+
+    /// The walls registered in any grid cell `segment` passes through,
+    /// deduplicated.
+    pub fn walls_near(&self, segment: Line) -> impl Iterator<Item = &Wall> {
+        let mut indices: Vec<usize> = cells_touched(segment, self.bounds)
+            .into_iter()
+            .filter_map(|cell| self.grid.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(move |index| &self.walls[index])
+    }
+}
+
+fn boundary_walls(bounds: Size) -> Vec<Wall> {
+    let (width, height) = (bounds.0 as f32, bounds.1 as f32);
+    vec![
+        Wall {
+            line: Line((width, 0.0), (width, height)),
+            normal: (1.0, 0.0),
+        },
+        Wall {
+            line: Line((0.0, 0.0), (0.0, height)),
+            normal: (-1.0, 0.0),
+        },
+        Wall {
+            line: Line((0.0, 0.0), (width, 0.0)),
+            normal: (0.0, -1.0),
+        },
+        Wall {
+            line: Line((0.0, height), (width, height)),
+            normal: (0.0, 1.0),
+        },
+    ]
+}
+
+/// A hand-sketched level layout: each line is a row of the board (top to
+/// bottom), each character a column (left to right), where `#` marks a
+/// solid platform cell and anything else is open space. Pegs are offset
+/// between rows so a falling node can't drop straight down the middle
+/// without deflecting off something.
+pub const DEFAULT_LAYOUT: &str = "\
+....................
+....................
+.......####.........
+....................
+..###...........###.
+....................
+.........####.......
+....................";
+
+/// Parses a text layout into interior obstacle walls, scaling the character
+/// grid up to `bounds`. Each contiguous run of `#` on a row becomes a
+/// single wall segment with a downward normal, exactly like the bottom
+/// boundary wall, so nodes falling under gravity land and rest on top of
+/// it instead of just bouncing off forever.
+pub fn obstacles_from_layout(layout: &str, bounds: Size) -> Vec<Wall> {
+    let rows: Vec<&str> = layout.lines().filter(|row| !row.is_empty()).collect();
+    if rows.is_empty() {
+        return Vec::new();
+    }
+    let row_count = rows.len();
+    let col_count = rows.iter().map(|row| row.chars().count()).max().unwrap_or(1);
+    let cell_width = bounds.0 as f32 / col_count as f32;
+    let cell_height = bounds.1 as f32 / row_count as f32;
+
+    let mut walls = Vec::new();
+    for (row_index, row) in rows.iter().enumerate() {
+        let cells: Vec<char> = row.chars().collect();
+        let mut column = 0;
+        while column < cells.len() {
+            if cells[column] != '#' {
+                column += 1;
+                continue;
+            }
+            let start_column = column;
+            while column < cells.len() && cells[column] == '#' {
+                column += 1;
+            }
+            let y = row_index as f32 * cell_height;
+            walls.push(Wall {
+                line: Line(
+                    (start_column as f32 * cell_width, y),
+                    (column as f32 * cell_width, y),
+                ),
+                normal: (0.0, 1.0),
+            });
+        }
+    }
+    walls
+}
+
+/// Every grid cell `line`'s segment actually passes through, via the
+/// supercover rasterizer, clamped to the level's bounds.
+fn cells_touched(line: Line, bounds: Size) -> Vec<Coordinate> {
+    supercover(point_to_cell(line.0, bounds), point_to_cell(line.1, bounds))
+}
+
+fn point_to_cell(point: Point, bounds: Size) -> Coordinate {
+    let x = (point.0.max(0.0) as usize).min(bounds.0.saturating_sub(1));
+    let y = (point.1.max(0.0) as usize).min(bounds.1.saturating_sub(1));
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walls_near_finds_boundary_wall() {
+        let level = Level::boundary((20, 100));
+        let segment = Line((19.0, 5.0), (19.9, 5.0));
+        let nearby: Vec<&Wall> = level.walls_near(segment).collect();
+        assert!(nearby.iter().any(|wall| wall.normal == (1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_walls_near_ignores_far_wall() {
+        let level = Level::boundary((20, 100));
+        let segment = Line((1.0, 5.0), (1.1, 5.0));
+        let nearby: Vec<&Wall> = level.walls_near(segment).collect();
+        assert!(!nearby.iter().any(|wall| wall.normal == (1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_obstacles_from_layout_merges_runs_and_scales_to_bounds() {
+        let layout = "....\n.##.\n....";
+        let walls = obstacles_from_layout(layout, (20, 30));
+
+        assert_eq!(walls.len(), 1);
+        let wall = walls[0];
+        assert_eq!(wall.normal, (0.0, 1.0));
+        assert_eq!(wall.line.0, (5.0, 10.0));
+        assert_eq!(wall.line.1, (15.0, 10.0));
+    }
+
+    #[test]
+    fn test_with_layout_includes_boundary_and_obstacle_walls() {
+        let level = Level::with_layout(DEFAULT_LAYOUT, (20, 100));
+        assert!(level.walls.len() > boundary_walls((20, 100)).len());
+    }
+}
@@ -0,0 +1,223 @@
+use std::f32::consts::PI;
+
+use rand::prelude::*;
+
+use crate::{get_new_position, point_to_board_coord, Angle, Coordinate, Level, Point, Size};
+use crate::{SimulationConfig, Velocity};
+
+/// Resample whenever the effective sample size drops below this fraction
+/// of the particle count, which skips the (lossy) resampling step while
+/// the weights are still fairly even.
+const RESAMPLE_THRESHOLD: f32 = 0.5;
+
+/// A floor on the per-particle likelihood, so a particle that's merely far
+/// from the observation (plausible right after a cold start, before the
+/// cloud has had a chance to converge) still contributes a nonzero weight
+/// instead of underflowing `f32` to exactly `0.0`. Without this, a node
+/// that starts more than a few `measurement_noise_std` from its first
+/// observation can have every particle underflow on the same tick, which
+/// permanently wedges `update` into the "all weights collapsed" branch.
+const MIN_LIKELIHOOD: f32 = 1e-6;
+
+#[derive(Clone, Copy)]
+struct Particle {
+    position: Point,
+    velocity: Velocity,
+    weight: f32,
+}
+
+/// Estimates a node's true continuous `(position, velocity)` from noisy,
+/// quantized board observations, via a weighted particle cloud: `predict`
+/// advances every particle through the same dynamics the real node uses,
+/// `update` reweights particles by how well they explain an observation,
+/// and `estimate` reports the weighted mean.
+pub struct ParticleFilter {
+    particles: Vec<Particle>,
+    process_noise_std: f32,
+    measurement_noise_std: f32,
+    last_good_estimate: (Point, Velocity),
+}
+
+impl ParticleFilter {
+    pub fn new(initial_position: Point, initial_velocity: Velocity, particle_count: usize) -> Self {
+        let weight = 1.0 / particle_count as f32;
+        let particles = (0..particle_count)
+            .map(|_| Particle {
+                position: initial_position,
+                velocity: initial_velocity,
+                weight,
+            })
+            .collect();
+        ParticleFilter {
+            particles,
+            process_noise_std: 0.2,
+            // Wide enough that a cold-start particle cloud (tens of board
+            // cells from the first observation) still gets a usable,
+            // non-underflowed likelihood signal to converge on.
+            measurement_noise_std: 2.5,
+            last_good_estimate: (initial_position, initial_velocity),
+        }
+    }
+
+    /// Advances every particle through the same swept-collision dynamics
+    /// the real node uses, perturbing each particle's velocity with
+    /// Gaussian process noise so the cloud spreads to cover plausible next
+    /// states.
+    pub fn predict(&mut self, level: &Level, config: &SimulationConfig) {
+        let mut rng = rand::thread_rng();
+        for particle in &mut self.particles {
+            particle.velocity.vx += config.gravity.0 + gaussian_noise(&mut rng, self.process_noise_std);
+            particle.velocity.vy += config.gravity.1 + gaussian_noise(&mut rng, self.process_noise_std);
+            let (position, velocity) =
+                get_new_position(particle.position, particle.velocity, level, config);
+            particle.position = position;
+            particle.velocity = velocity;
+        }
+    }
+
+    /// Reweights every particle by the likelihood of `observation` (a
+    /// noisy, quantized board coordinate) given the particle's own
+    /// projection onto the board, then resamples if the weights have
+    /// grown too uneven to be useful. Falls back to reinitializing the
+    /// cloud around the last good estimate if the total weight drops to
+    /// `f32::EPSILON` or below. Particles that are merely far from the
+    /// observation keep at least `MIN_LIKELIHOOD` and do not trigger it.
+    pub fn update(&mut self, observation: Coordinate, resolution: Size, play_area: Size) {
+        for particle in &mut self.particles {
+            let projected = point_to_board_coord(particle.position, resolution, play_area);
+            let dx = projected.0 as f32 - observation.0 as f32;
+            let dy = projected.1 as f32 - observation.1 as f32;
+            let distance_sq = dx * dx + dy * dy;
+            let likelihood =
+                (-distance_sq / (2.0 * self.measurement_noise_std.powi(2))).exp();
+            particle.weight *= likelihood.max(MIN_LIKELIHOOD);
+        }
+
+        let total_weight: f32 = self.particles.iter().map(|particle| particle.weight).sum();
+        if total_weight <= f32::EPSILON {
+            self.reinitialize_around(self.last_good_estimate);
+            return;
+        }
+        for particle in &mut self.particles {
+            particle.weight /= total_weight;
+        }
+
+        self.last_good_estimate = self.estimate();
+
+        if self.effective_sample_size() < self.particles.len() as f32 * RESAMPLE_THRESHOLD {
+            self.resample();
+        }
+    }
+
+    /// The weighted-mean position and velocity across all particles.
+    pub fn estimate(&self) -> (Point, Velocity) {
+        let mut position = (0.0, 0.0);
+        let mut velocity = (0.0, 0.0);
+        for particle in &self.particles {
+            position.0 += particle.position.0 * particle.weight;
+            position.1 += particle.position.1 * particle.weight;
+            velocity.0 += particle.velocity.vx * particle.weight;
+            velocity.1 += particle.velocity.vy * particle.weight;
+        }
+        (position, Velocity { vx: velocity.0, vy: velocity.1 })
+    }
+
+    fn effective_sample_size(&self) -> f32 {
+        1.0 / self
+            .particles
+            .iter()
+            .map(|particle| particle.weight * particle.weight)
+            .sum::<f32>()
+    }
+
+    /// Draws a new particle cloud with replacement, proportional to
+    /// weight, and resets every weight to `1 / particle_count`.
+    fn resample(&mut self) {
+        let mut rng = rand::thread_rng();
+        let count = self.particles.len();
+
+        let mut cumulative_weights = Vec::with_capacity(count);
+        let mut running_weight = 0.0;
+        for particle in &self.particles {
+            running_weight += particle.weight;
+            cumulative_weights.push(running_weight);
+        }
+
+        let reset_weight = 1.0 / count as f32;
+        self.particles = (0..count)
+            .map(|_| {
+                let target = rng.gen::<f32>();
+                let index = cumulative_weights
+                    .partition_point(|&cumulative| cumulative < target)
+                    .min(count - 1);
+                let mut particle = self.particles[index];
+                particle.weight = reset_weight;
+                particle
+            })
+            .collect();
+    }
+
+    fn reinitialize_around(&mut self, (position, velocity): (Point, Velocity)) {
+        let mut rng = rand::thread_rng();
+        let count = self.particles.len();
+        for particle in &mut self.particles {
+            particle.position = (
+                position.0 + gaussian_noise(&mut rng, self.measurement_noise_std),
+                position.1 + gaussian_noise(&mut rng, self.measurement_noise_std),
+            );
+            particle.velocity = velocity;
+            particle.weight = 1.0 / count as f32;
+        }
+    }
+}
+
+/// Samples from a zero-mean Gaussian with the given standard deviation via
+/// the Box-Muller transform. The repo has no dedicated distributions
+/// dependency, so this avoids adding one for a single call site.
+fn gaussian_noise(rng: &mut impl Rng, std_dev: f32) -> f32 {
+    let u1: f32 = rng.gen::<f32>().max(f32::EPSILON);
+    let u2: f32 = rng.gen::<f32>();
+    let magnitude = (-2.0 * u1.ln()).sqrt();
+    magnitude * Angle::from_radians(2.0 * PI * u2).cos() * std_dev
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_starts_at_initial_state() {
+        let filter = ParticleFilter::new((5.0, 5.0), Velocity { vx: 1.0, vy: 0.0 }, 200);
+        let (position, velocity) = filter.estimate();
+
+        // f32 accumulation of 200 equal-weighted particles doesn't land on
+        // the initial value exactly, so this needs a looser tolerance than
+        // the usual 1e-5.
+        assert!((position.0 - 5.0).abs() < 1e-4);
+        assert!((position.1 - 5.0).abs() < 1e-4);
+        assert!((velocity.vx - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_update_converges_toward_repeated_observation() {
+        let level = Level::boundary((20, 100));
+        // Zero gravity: the observed node here is stationary, and gravity
+        // would otherwise bias every particle's velocity downward each
+        // tick with nothing to correct it, since the measurement only
+        // informs position, not velocity.
+        let config = SimulationConfig {
+            gravity: (0.0, 0.0),
+            ..SimulationConfig::default()
+        };
+        let mut filter = ParticleFilter::new((2.0, 2.0), Velocity { vx: 0.0, vy: 0.0 }, 500);
+
+        for _ in 0..20 {
+            filter.predict(&level, &config);
+            filter.update((10, 10), (20, 100), (20, 100));
+        }
+
+        let (position, _) = filter.estimate();
+        assert!((position.0 - 10.0).abs() < 3.0);
+        assert!((position.1 - 10.0).abs() < 3.0);
+    }
+}
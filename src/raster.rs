@@ -0,0 +1,82 @@
+use crate::Coordinate;
+
+/// Every board cell the segment from `start` to `end` passes through,
+/// including cells it only clips diagonally ("supercover" rasterization).
+/// Used both to draw motion trails and to enumerate which grid cells a
+/// travel segment overlaps for broad-phase collision queries.
+pub fn supercover(start: Coordinate, end: Coordinate) -> Vec<Coordinate> {
+    let (x1, y1) = (start.0 as i64, start.1 as i64);
+    let (x2, y2) = (end.0 as i64, end.1 as i64);
+
+    let nx = (x2 - x1).abs();
+    let ny = (y2 - y1).abs();
+    let step_x = (x2 - x1).signum();
+    let step_y = (y2 - y1).signum();
+
+    let mut x = x1;
+    let mut y = y1;
+    let mut ix = 0;
+    let mut iy = 0;
+
+    let mut cells = Vec::with_capacity((nx.max(ny) + 1) as usize);
+    cells.push((x, y));
+
+    while ix < nx || iy < ny {
+        let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+        if decision == 0 {
+            x += step_x;
+            y += step_y;
+            ix += 1;
+            iy += 1;
+        } else if decision < 0 {
+            x += step_x;
+            ix += 1;
+        } else {
+            y += step_y;
+            iy += 1;
+        }
+        cells.push((x, y));
+    }
+
+    cells
+        .into_iter()
+        .map(|(x, y)| (x.max(0) as usize, y.max(0) as usize))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supercover_straight_line() {
+        let cells = supercover((0, 0), (3, 0));
+        assert_eq!(cells, vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn test_supercover_diagonal() {
+        let cells = supercover((0, 0), (2, 2));
+        assert_eq!(cells, vec![(0, 0), (1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn test_supercover_has_no_gaps() {
+        // A shallow slope still moves mostly along one axis, but it must
+        // never skip past a cell it should have clipped diagonally.
+        let cells = supercover((0, 0), (4, 1));
+        for window in cells.windows(2) {
+            let (x1, y1) = window[0];
+            let (x2, y2) = window[1];
+            assert!((x2 as i64 - x1 as i64).abs() <= 1);
+            assert!((y2 as i64 - y1 as i64).abs() <= 1);
+        }
+        assert_eq!(cells.first(), Some(&(0, 0)));
+        assert_eq!(cells.last(), Some(&(4, 1)));
+    }
+
+    #[test]
+    fn test_supercover_single_point() {
+        assert_eq!(supercover((5, 5), (5, 5)), vec![(5, 5)]);
+    }
+}